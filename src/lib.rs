@@ -1,57 +1,219 @@
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 
+/// How a tree turns raw bytes into digests: a leaf hash from its data, and a
+/// branch hash from its children's digests. Swapping the strategy swaps the
+/// tree's security properties without touching `MerkleTree`/`Node`/`Proof`.
+trait MerkleHasher {
+    type Digest: Clone + Eq + Hash + fmt::Debug;
+
+    fn hash_leaf(data: &[u8]) -> Self::Digest;
+    fn hash_branch(children: &[&Self::Digest]) -> Self::Digest;
+}
+
+/// Fast, non-cryptographic strategy built on [`DefaultHasher`]. Good enough
+/// for tests; not collision-resistant and not stable across platforms.
+#[derive(Debug, Clone)]
+struct DefaultHashStrategy;
+
+impl MerkleHasher for DefaultHashStrategy {
+    type Digest = u64;
+
+    fn hash_leaf(data: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_branch(children: &[&u64]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for child in children {
+            child.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+/// Cryptographic strategy: SHA-256, double-hashing concatenated children the
+/// way Bitcoin/Zcash merkle trees do (hashing the hash guards against
+/// length-extension attacks on the branch digest).
+#[derive(Debug, Clone)]
+struct Sha256Hasher;
+
+impl MerkleHasher for Sha256Hasher {
+    type Digest = [u8; 32];
+
+    fn hash_leaf(data: &[u8]) -> [u8; 32] {
+        sha256(data)
+    }
+
+    fn hash_branch(children: &[&[u8; 32]]) -> [u8; 32] {
+        let mut concatenated = Vec::with_capacity(children.len() * 32);
+        for child in children {
+            concatenated.extend_from_slice(child.as_slice());
+        }
+        sha256(&sha256(&concatenated))
+    }
+}
+
+/// Self-contained SHA-256 (FIPS 180-4), since this crate does not depend on
+/// a digest crate.
+fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+        0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+
+    let bit_len = (data.len() as u64) * 8;
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&bit_len.to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([chunk[4 * i], chunk[4 * i + 1], chunk[4 * i + 2], chunk[4 * i + 3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let (mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh) =
+            (h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7]);
+
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ ((!e) & g);
+            let temp1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+        h[5] = h[5].wrapping_add(f);
+        h[6] = h[6].wrapping_add(g);
+        h[7] = h[7].wrapping_add(hh);
+    }
+
+    let mut out = [0u8; 32];
+    for (i, word) in h.iter().enumerate() {
+        out[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
 #[derive(Debug)]
-struct MerkleTree {
-    root: Option<Node>,
+struct MerkleTree<H: MerkleHasher = Sha256Hasher> {
+    root: Option<Node<H>>,
+    /// Leaf digests in the original `data` order passed to `new`, used to
+    /// resolve the leaf indices `multiproof` takes.
+    leaf_hashes: Vec<H::Digest>,
 }
 
-#[derive(Debug, Clone, Hash)]
-enum Node {
-    Leaf { hash: u64, data: String },
-    Branch { hash: u64, left: Box<Node>, middle: Box<Node>, right: Box<Node> },
+#[derive(Debug)]
+enum Node<H: MerkleHasher> {
+    Leaf { hash: H::Digest, data: String },
+    Branch { hash: H::Digest, left: Box<Node<H>>, middle: Box<Node<H>>, right: Box<Node<H>> },
 }
 
-impl Node {
-    fn get_hash(&self) -> u64 {
+// Written by hand rather than derived: `derive(Clone)` would bound `H: Clone`,
+// but `build_tree` only knows `H: MerkleHasher` — it never needs the
+// strategy marker itself to be `Clone`, only its `Digest`, which the trait
+// already guarantees.
+impl<H: MerkleHasher> Clone for Node<H> {
+    fn clone(&self) -> Self {
         match self {
-            Node::Leaf { hash, .. } => *hash,
-            Node::Branch { hash, .. } => *hash,
+            Node::Leaf { hash, data } => Node::Leaf { hash: hash.clone(), data: data.clone() },
+            Node::Branch { hash, left, middle, right } => Node::Branch {
+                hash: hash.clone(),
+                left: left.clone(),
+                middle: middle.clone(),
+                right: right.clone(),
+            },
+        }
+    }
+}
+
+impl<H: MerkleHasher> Node<H> {
+    fn get_hash(&self) -> &H::Digest {
+        match self {
+            Node::Leaf { hash, .. } => hash,
+            Node::Branch { hash, .. } => hash,
         }
     }
 }
 
 #[derive(Debug)]
-struct Proof {
-    target_hash: u64,
-    proof_hashes: Vec<u64>,
-    proof_directions: Vec<Direction>,
+struct Proof<H: MerkleHasher> {
+    target_hash: H::Digest,
+    entries: Vec<ProofEntry<H>>,
 }
 
+/// One level of a proof path: the target's slot among the branch's three
+/// children, plus the digests of the other two children in their original
+/// left/middle/right order (with the target's slot omitted).
 #[derive(Debug)]
+struct ProofEntry<H: MerkleHasher> {
+    position: Direction,
+    siblings: (H::Digest, H::Digest),
+}
+
+#[derive(Debug, Clone, Copy)]
 enum Direction {
     Left,
     Middle,
     Right,
 }
 
-impl MerkleTree {
+impl<H: MerkleHasher> MerkleTree<H> {
     fn new(data: Vec<String>) -> Self {
         let leaves = data.into_iter()
             .map(|d| {
-                let mut hasher = DefaultHasher::new();
-                d.hash(&mut hasher);
-                let hash = hasher.finish();
+                let hash = H::hash_leaf(d.as_bytes());
                 Node::Leaf { hash, data: d }
             })
-            .collect::<Vec<Node>>();
+            .collect::<Vec<Node<H>>>();
+
+        let leaf_hashes = leaves.iter().map(|leaf| leaf.get_hash().clone()).collect();
 
         let root = MerkleTree::build_tree(leaves);
-        MerkleTree { root }
+        MerkleTree { root, leaf_hashes }
     }
 
-    fn build_tree(mut nodes: Vec<Node>) -> Option<Node> {
+    fn build_tree(mut nodes: Vec<Node<H>>) -> Option<Node<H>> {
         if nodes.is_empty() {
             return None;
         }
@@ -64,12 +226,7 @@ impl MerkleTree {
                 let middle = nodes.pop().unwrap_or_else(|| left.clone());
                 let right = nodes.pop().unwrap_or_else(|| left.clone());
 
-                let mut hasher = DefaultHasher::new();
-
-                left.hash(&mut hasher);
-                middle.hash(&mut hasher);
-                right.hash(&mut hasher);
-                let hash = hasher.finish();
+                let hash = H::hash_branch(&[left.get_hash(), middle.get_hash(), right.get_hash()]);
 
                 let branch = Node::Branch {
                     hash,
@@ -87,74 +244,841 @@ impl MerkleTree {
         nodes.pop()
     }
 
-    fn root_hash(&self) -> Option<u64> {
-        self.root.as_ref().map(|n| match n {
-            Node::Leaf { hash, .. } => *hash,
-            Node::Branch { hash, .. } => *hash,
-        })
+    fn root_hash(&self) -> Option<H::Digest> {
+        self.root.as_ref().map(|n| n.get_hash().clone())
     }
 
-    fn calculate_hash(&self, data: &str) -> Option<u64> {
-        let mut hasher = DefaultHasher::new();
-        data.hash(&mut hasher);
-        Some(hasher.finish())
+    fn calculate_hash(&self, data: &str) -> Option<H::Digest> {
+        Some(H::hash_leaf(data.as_bytes()))
     }
 
 
-    fn proof(&self, data: &str) -> Option<Proof> {
+    fn proof(&self, data: &str) -> Option<Proof<H>> {
         let target_hash = self.calculate_hash(data)?;
 
-        let mut proof_hashes = Vec::new();
-        let mut proof_directions = Vec::new();
+        let mut entries = Vec::new();
 
-        let result = self.proof_recursion(self.root.as_ref()?, target_hash, &mut proof_hashes, &mut proof_directions);
+        let found = self.proof_recursion(self.root.as_ref()?, &target_hash, &mut entries);
 
-        if result {
+        if found {
             Some(Proof {
                 target_hash,
-                proof_hashes,
-                proof_directions,
+                entries,
             })
         } else {
             None
         }
     }
 
-    fn proof_recursion(&self, node: &Node, target_hash: u64, proof_hashes: &mut Vec<u64>, proof_directions: &mut Vec<Direction>) -> bool {
+    fn proof_recursion(&self, node: &Node<H>, target_hash: &H::Digest, entries: &mut Vec<ProofEntry<H>>) -> bool {
         match node {
-            Node::Leaf { hash, .. } => *hash == target_hash,
-            Node::Branch { hash, left, middle, right } => {
-                if *hash == target_hash {
+            Node::Leaf { hash, .. } => hash == target_hash,
+            Node::Branch { left, middle, right, .. } => {
+                let found_left = self.proof_recursion(left, target_hash, entries);
+                let found_middle = !found_left && self.proof_recursion(middle, target_hash, entries);
+                let found_right = !found_left && !found_middle && self.proof_recursion(right, target_hash, entries);
+
+                if found_left {
+                    entries.push(ProofEntry { position: Direction::Left, siblings: (middle.get_hash().clone(), right.get_hash().clone()) });
+                    true
+                } else if found_middle {
+                    entries.push(ProofEntry { position: Direction::Middle, siblings: (left.get_hash().clone(), right.get_hash().clone()) });
+                    true
+                } else if found_right {
+                    entries.push(ProofEntry { position: Direction::Right, siblings: (left.get_hash().clone(), middle.get_hash().clone()) });
                     true
                 } else {
-                    let found_left = self.proof_recursion(left, target_hash, proof_hashes, proof_directions);
-                    let found_middle = self.proof_recursion(middle, target_hash, proof_hashes, proof_directions);
-                    let found_right = self.proof_recursion(right, target_hash, proof_hashes, proof_directions);
-
-                    if found_left {
-                        proof_hashes.push(left.get_hash());
-                        proof_directions.push(Direction::Left);
-                        true
-                    } else if found_middle {
-                        proof_hashes.push(middle.get_hash());
-                        proof_directions.push(Direction::Middle);
-                        true
-                    } else if found_right {
-                        proof_hashes.push(right.get_hash());
-                        proof_directions.push(Direction::Right);
-                        true
+                    false
+                }
+            }
+        }
+    }
+}
+
+impl<H: MerkleHasher> Proof<H> {
+    /// Folds `target_hash` up through the recorded entries, reassembling each
+    /// branch's three child digests (candidate in its recorded `position`,
+    /// siblings in the other two slots) and re-hashing them the same way
+    /// `build_tree` does, then compares the result against `root`.
+    fn verify(&self, root: &H::Digest) -> bool {
+        let mut candidate = self.target_hash.clone();
+
+        for entry in &self.entries {
+            let (left, middle, right) = match entry.position {
+                Direction::Left => (candidate.clone(), entry.siblings.0.clone(), entry.siblings.1.clone()),
+                Direction::Middle => (entry.siblings.0.clone(), candidate.clone(), entry.siblings.1.clone()),
+                Direction::Right => (entry.siblings.0.clone(), entry.siblings.1.clone(), candidate.clone()),
+            };
+
+            candidate = H::hash_branch(&[&left, &middle, &right]);
+        }
+
+        &candidate == root
+    }
+}
+
+impl<H: MerkleHasher> MerkleTree<H> {
+    /// Proves several leaves (by their original index in `data`) at once,
+    /// pruning any subtree that contains none of them to a single hash
+    /// instead of repeating it in a separate proof per leaf.
+    fn multiproof(&self, targets: &[usize]) -> PartialProof<H> {
+        let mut target_set: HashSet<H::Digest> = targets.iter()
+            .map(|&index| self.leaf_hashes[index].clone())
+            .collect();
+
+        let result = match &self.root {
+            Some(root) => Self::multiproof_recursion(root, &mut target_set),
+            None => TraversalResult { matched: false, bits: Vec::new(), hashes: Vec::new(), target_hashes: Vec::new() },
+        };
+
+        PartialProof {
+            leaf_count: self.leaf_hashes.len(),
+            bits: result.bits,
+            hashes: result.hashes,
+            target_hashes: result.target_hashes,
+        }
+    }
+
+    /// Depth-first: a branch descends (bit `true`) only if one of its three
+    /// children does, otherwise it's pruned (bit `false`, hash recorded
+    /// verbatim) regardless of whether any of its children are duplicated
+    /// padding from `build_tree`.
+    ///
+    /// `targets` is drained as leaves are matched: `build_tree` pads short
+    /// groups by cloning a real leaf into the sibling slots, so without this
+    /// a single requested leaf that lands in a padded slot would be "found"
+    /// once per clone instead of once. Removing a hash from the set the
+    /// first time it's matched means later clones of that same leaf are
+    /// correctly treated as non-targets and pruned like any other subtree.
+    fn multiproof_recursion(node: &Node<H>, targets: &mut HashSet<H::Digest>) -> TraversalResult<H> {
+        match node {
+            Node::Leaf { hash, .. } => {
+                if targets.remove(hash) {
+                    TraversalResult { matched: true, bits: vec![true], hashes: vec![], target_hashes: vec![hash.clone()] }
+                } else {
+                    TraversalResult { matched: false, bits: vec![false], hashes: vec![hash.clone()], target_hashes: vec![] }
+                }
+            }
+            Node::Branch { hash, left, middle, right } => {
+                let left = Self::multiproof_recursion(left, targets);
+                let middle = Self::multiproof_recursion(middle, targets);
+                let right = Self::multiproof_recursion(right, targets);
+
+                if left.matched || middle.matched || right.matched {
+                    let mut bits = vec![true];
+                    bits.extend(left.bits);
+                    bits.extend(middle.bits);
+                    bits.extend(right.bits);
+
+                    let mut hashes = left.hashes;
+                    hashes.extend(middle.hashes);
+                    hashes.extend(right.hashes);
+
+                    let mut target_hashes = left.target_hashes;
+                    target_hashes.extend(middle.target_hashes);
+                    target_hashes.extend(right.target_hashes);
+
+                    TraversalResult { matched: true, bits, hashes, target_hashes }
+                } else {
+                    TraversalResult { matched: false, bits: vec![false], hashes: vec![hash.clone()], target_hashes: vec![] }
+                }
+            }
+        }
+    }
+}
+
+/// Accumulator threaded bottom-up by `multiproof_recursion`.
+struct TraversalResult<H: MerkleHasher> {
+    matched: bool,
+    bits: Vec<bool>,
+    hashes: Vec<H::Digest>,
+    target_hashes: Vec<H::Digest>,
+}
+
+/// A compact proof for multiple leaves at once (Bitcoin/Zcash-style partial
+/// merkle tree), produced by [`MerkleTree::multiproof`].
+#[derive(Debug)]
+struct PartialProof<H: MerkleHasher> {
+    leaf_count: usize,
+    /// One bit per visited node, in depth-first left/middle/right order:
+    /// `true` means the node is on a path to a target (recurse for a
+    /// branch, a target leaf itself at the bottom), `false` means the
+    /// subtree was pruned and its hash recorded verbatim.
+    bits: Vec<bool>,
+    /// Hashes of pruned subtrees, consumed in the order their `false` bit
+    /// was emitted.
+    hashes: Vec<H::Digest>,
+    /// Target leaf hashes, consumed in the order their `true` bit was
+    /// emitted at the bottom of the tree.
+    target_hashes: Vec<H::Digest>,
+}
+
+impl<H: MerkleHasher> PartialProof<H> {
+    fn verify(&self, root: &H::Digest) -> bool {
+        let height = Self::height(self.leaf_count);
+        let mut bit_idx = 0;
+        let mut hash_idx = 0;
+        let mut target_idx = 0;
+
+        let computed = Self::replay(height, &self.bits, &self.hashes, &self.target_hashes, &mut bit_idx, &mut hash_idx, &mut target_idx);
+
+        computed.as_ref() == Some(root)
+            && bit_idx == self.bits.len()
+            && hash_idx == self.hashes.len()
+            && target_idx == self.target_hashes.len()
+    }
+
+    /// Number of branch levels above the leaves, matching the number of
+    /// `while nodes.len() > 1` rounds `build_tree` would run for `leaf_count`
+    /// leaves.
+    fn height(leaf_count: usize) -> usize {
+        let mut n = leaf_count;
+        let mut height = 0;
+
+        while n > 1 {
+            n = n.div_ceil(3);
+            height += 1;
+        }
+
+        height
+    }
+
+    fn replay(
+        depth: usize,
+        bits: &[bool],
+        hashes: &[H::Digest],
+        target_hashes: &[H::Digest],
+        bit_idx: &mut usize,
+        hash_idx: &mut usize,
+        target_idx: &mut usize,
+    ) -> Option<H::Digest> {
+        let bit = *bits.get(*bit_idx)?;
+        *bit_idx += 1;
+
+        if !bit {
+            let hash = hashes.get(*hash_idx)?.clone();
+            *hash_idx += 1;
+            return Some(hash);
+        }
+
+        if depth == 0 {
+            let hash = target_hashes.get(*target_idx)?.clone();
+            *target_idx += 1;
+            return Some(hash);
+        }
+
+        let left = Self::replay(depth - 1, bits, hashes, target_hashes, bit_idx, hash_idx, target_idx)?;
+        let middle = Self::replay(depth - 1, bits, hashes, target_hashes, bit_idx, hash_idx, target_idx)?;
+        let right = Self::replay(depth - 1, bits, hashes, target_hashes, bit_idx, hash_idx, target_idx)?;
+
+        Some(H::hash_branch(&[&left, &middle, &right]))
+    }
+}
+
+impl<H: MerkleHasher> MerkleTree<H> {
+    /// Renders the whole tree as a Graphviz `digraph`: each branch is a
+    /// vertex labeled with its (truncated) hash, with `L`/`M`/`R` edges to
+    /// its children; each leaf also shows its `data`. An edge to a child
+    /// that `build_tree` cloned from `left` to pad an incomplete group is
+    /// drawn dashed.
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph MerkleTree {\n");
+
+        if let Some(root) = &self.root {
+            let mut next_id = 0;
+            root.write_dot(&mut dot, &mut next_id);
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl<H: MerkleHasher> Node<H> {
+    /// Emits this node (and, for a branch, its whole subtree) as Graphviz
+    /// statements and returns the id assigned to this node, so the caller
+    /// can draw an edge to it.
+    fn write_dot(&self, dot: &mut String, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+
+        match self {
+            Node::Leaf { hash, data } => {
+                dot.push_str(&format!(
+                    "  n{id} [label=\"{}\\n{}\", shape=box];\n",
+                    truncate_hash(hash),
+                    escape_dot_label(data),
+                ));
+            }
+            Node::Branch { hash, left, middle, right } => {
+                dot.push_str(&format!("  n{id} [label=\"{}\"];\n", truncate_hash(hash)));
+
+                let left_id = left.write_dot(dot, next_id);
+                let middle_id = middle.write_dot(dot, next_id);
+                let right_id = right.write_dot(dot, next_id);
+
+                let middle_padding = middle.get_hash() == left.get_hash();
+                let right_padding = right.get_hash() == left.get_hash() || right.get_hash() == middle.get_hash();
+
+                dot.push_str(&format!("  n{id} -> n{left_id} [label=\"L\"];\n"));
+                dot.push_str(&dot_edge(id, middle_id, "M", middle_padding));
+                dot.push_str(&dot_edge(id, right_id, "R", right_padding));
+            }
+        }
+
+        id
+    }
+}
+
+fn dot_edge(from: usize, to: usize, label: &str, padding: bool) -> String {
+    if padding {
+        format!("  n{from} -> n{to} [label=\"{label}\", style=dashed];\n")
+    } else {
+        format!("  n{from} -> n{to} [label=\"{label}\"];\n")
+    }
+}
+
+fn truncate_hash<D: fmt::Debug>(hash: &D) -> String {
+    let full = format!("{:?}", hash);
+
+    if full.len() > 12 {
+        format!("{}…", &full[..12])
+    } else {
+        full
+    }
+}
+
+fn escape_dot_label(data: &str) -> String {
+    data.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// A fixed-depth ternary merkle tree over the index space `0..3^DEPTH`,
+/// where almost every leaf is empty. Only nodes on a path to a non-empty
+/// leaf are ever stored; every other subtree is represented implicitly by
+/// `zero_hashes[level]`, so an all-empty tree costs O(DEPTH) memory instead
+/// of O(3^DEPTH).
+#[derive(Debug)]
+struct SparseMerkleTree<H: MerkleHasher, const DEPTH: usize> {
+    /// `zero_hashes[0]` is the hash of an empty leaf; `zero_hashes[d + 1]` is
+    /// the branch hash of three copies of `zero_hashes[d]`.
+    zero_hashes: Vec<H::Digest>,
+    /// Non-empty nodes only, keyed by `(level, index at that level)`. Level 0
+    /// is the leaves, level `DEPTH` is the root (always at index 0).
+    nodes: HashMap<(usize, usize), H::Digest>,
+    /// Raw data for filled leaves, keyed by leaf index.
+    leaves: HashMap<usize, String>,
+}
+
+impl<H: MerkleHasher, const DEPTH: usize> SparseMerkleTree<H, DEPTH> {
+    fn new() -> Self {
+        let mut zero_hashes = Vec::with_capacity(DEPTH + 1);
+        zero_hashes.push(H::hash_leaf(&[]));
+
+        for level in 0..DEPTH {
+            let zero = zero_hashes[level].clone();
+            zero_hashes.push(H::hash_branch(&[&zero, &zero, &zero]));
+        }
+
+        SparseMerkleTree {
+            zero_hashes,
+            nodes: HashMap::new(),
+            leaves: HashMap::new(),
+        }
+    }
+
+    fn node_hash(&self, level: usize, index: usize) -> H::Digest {
+        self.nodes.get(&(level, index))
+            .cloned()
+            .unwrap_or_else(|| self.zero_hashes[level].clone())
+    }
+
+    /// Updates only the O(DEPTH) nodes on the path from `index` to the root.
+    fn insert(&mut self, index: usize, data: String) {
+        let leaf_hash = H::hash_leaf(data.as_bytes());
+        self.nodes.insert((0, index), leaf_hash);
+        self.leaves.insert(index, data);
+
+        let mut idx = index;
+        for level in 0..DEPTH {
+            let group_start = (idx / 3) * 3;
+            let left = self.node_hash(level, group_start);
+            let middle = self.node_hash(level, group_start + 1);
+            let right = self.node_hash(level, group_start + 2);
+            let hash = H::hash_branch(&[&left, &middle, &right]);
+
+            idx /= 3;
+            if hash == self.zero_hashes[level + 1] {
+                self.nodes.remove(&(level + 1, idx));
+            } else {
+                self.nodes.insert((level + 1, idx), hash);
+            }
+        }
+    }
+
+    fn root_hash(&self) -> H::Digest {
+        self.node_hash(DEPTH, 0)
+    }
+
+    /// A proof for whatever is at `index`: if the leaf is filled this is a
+    /// membership proof for its data; if the leaf is empty `target_hash` is
+    /// `zero_hashes[0]` and the same proof is a non-membership proof.
+    fn proof(&self, index: usize) -> Proof<H> {
+        let target_hash = self.node_hash(0, index);
+        let mut entries = Vec::with_capacity(DEPTH);
+
+        let mut idx = index;
+        for level in 0..DEPTH {
+            let group_start = (idx / 3) * 3;
+            let left = self.node_hash(level, group_start);
+            let middle = self.node_hash(level, group_start + 1);
+            let right = self.node_hash(level, group_start + 2);
+
+            let (position, siblings) = match idx - group_start {
+                0 => (Direction::Left, (middle, right)),
+                1 => (Direction::Middle, (left, right)),
+                _ => (Direction::Right, (left, middle)),
+            };
+
+            entries.push(ProofEntry { position, siblings });
+            idx /= 3;
+        }
+
+        Proof { target_hash, entries }
+    }
+}
+
+/// A node as it lives in a [`NodeStore`]: children are referenced by digest
+/// rather than embedded, so identical subtrees across different commits
+/// share a single stored entry.
+#[derive(Debug)]
+enum StoredNode<H: MerkleHasher> {
+    Leaf { data: String },
+    Branch { left: H::Digest, middle: H::Digest, right: H::Digest },
+}
+
+// See the matching impl on `Node`: `derive(Clone)` would bound `H: Clone`,
+// but callers only have `H: MerkleHasher` in scope.
+impl<H: MerkleHasher> Clone for StoredNode<H> {
+    fn clone(&self) -> Self {
+        match self {
+            StoredNode::Leaf { data } => StoredNode::Leaf { data: data.clone() },
+            StoredNode::Branch { left, middle, right } => StoredNode::Branch {
+                left: left.clone(),
+                middle: middle.clone(),
+                right: right.clone(),
+            },
+        }
+    }
+}
+
+/// A content-addressed backend for [`VersionedMerkleTree`]: nodes are looked
+/// up and written by their own hash, so writing an already-present node is a
+/// no-op and unchanged subtrees are shared across commits for free.
+trait NodeStore<H: MerkleHasher> {
+    fn get(&self, hash: &H::Digest) -> Option<StoredNode<H>>;
+    fn put(&mut self, hash: H::Digest, node: StoredNode<H>);
+}
+
+#[derive(Debug)]
+struct InMemoryNodeStore<H: MerkleHasher> {
+    nodes: HashMap<H::Digest, StoredNode<H>>,
+}
+
+impl<H: MerkleHasher> InMemoryNodeStore<H> {
+    fn new() -> Self {
+        InMemoryNodeStore { nodes: HashMap::new() }
+    }
+}
+
+impl<H: MerkleHasher> NodeStore<H> for InMemoryNodeStore<H> {
+    fn get(&self, hash: &H::Digest) -> Option<StoredNode<H>> {
+        self.nodes.get(hash).cloned()
+    }
+
+    fn put(&mut self, hash: H::Digest, node: StoredNode<H>) {
+        self.nodes.insert(hash, node);
+    }
+}
+
+/// A merkle tree whose nodes live in a [`NodeStore`] rather than in memory,
+/// keeping every past root so an older state can still be proven against.
+#[derive(Debug)]
+struct VersionedMerkleTree<H: MerkleHasher, S: NodeStore<H> = InMemoryNodeStore<H>> {
+    store: S,
+    roots: Vec<H::Digest>,
+}
+
+impl<H: MerkleHasher, S: NodeStore<H>> VersionedMerkleTree<H, S> {
+    fn new(store: S) -> Self {
+        VersionedMerkleTree { store, roots: Vec::new() }
+    }
+
+    /// Builds a tree from `data`, writes whatever nodes aren't already in
+    /// the store, records the new root in the history, and returns it.
+    fn commit(&mut self, data: Vec<String>) -> H::Digest {
+        let leaves = data.into_iter()
+            .map(|d| Node::Leaf { hash: H::hash_leaf(d.as_bytes()), data: d })
+            .collect::<Vec<Node<H>>>();
+
+        let root = MerkleTree::<H>::build_tree(leaves).expect("commit requires at least one leaf");
+        let root_hash = Self::persist(&mut self.store, &root);
+        self.roots.push(root_hash.clone());
+        root_hash
+    }
+
+    fn persist(store: &mut S, node: &Node<H>) -> H::Digest {
+        let hash = node.get_hash().clone();
+
+        if store.get(&hash).is_none() {
+            match node {
+                Node::Leaf { data, .. } => {
+                    store.put(hash.clone(), StoredNode::Leaf { data: data.clone() });
+                }
+                Node::Branch { left, middle, right, .. } => {
+                    let left_hash = Self::persist(store, left);
+                    let middle_hash = Self::persist(store, middle);
+                    let right_hash = Self::persist(store, right);
+                    store.put(hash.clone(), StoredNode::Branch { left: left_hash, middle: middle_hash, right: right_hash });
+                }
+            }
+        }
+
+        hash
+    }
+
+    /// Walks the historical tree identified by `root` (which need not be the
+    /// latest one) and produces a proof valid against that specific root.
+    fn proof_at(&self, root: &H::Digest, data: &str) -> Option<Proof<H>> {
+        let target_hash = H::hash_leaf(data.as_bytes());
+        let mut entries = Vec::new();
+        let found = self.find_path(root, &target_hash, &mut entries)?;
+
+        if found {
+            Some(Proof { target_hash, entries })
+        } else {
+            None
+        }
+    }
+
+    fn find_path(&self, hash: &H::Digest, target_hash: &H::Digest, entries: &mut Vec<ProofEntry<H>>) -> Option<bool> {
+        match self.store.get(hash)? {
+            StoredNode::Leaf { .. } => Some(hash == target_hash),
+            StoredNode::Branch { left, middle, right } => {
+                let found_left = self.find_path(&left, target_hash, entries)?;
+                let found_middle = !found_left && self.find_path(&middle, target_hash, entries)?;
+                let found_right = !found_left && !found_middle && self.find_path(&right, target_hash, entries)?;
+
+                if found_left {
+                    entries.push(ProofEntry { position: Direction::Left, siblings: (middle.clone(), right.clone()) });
+                } else if found_middle {
+                    entries.push(ProofEntry { position: Direction::Middle, siblings: (left.clone(), right.clone()) });
+                } else if found_right {
+                    entries.push(ProofEntry { position: Direction::Right, siblings: (left.clone(), middle.clone()) });
+                }
+
+                Some(found_left || found_middle || found_right)
+            }
+        }
+    }
+}
+
+/// SQLite-backed [`NodeStore`] for [`Sha256Hasher`], enabled with the
+/// `sqlite` feature, so stored nodes survive a restart. The list of past
+/// roots (`VersionedMerkleTree::roots`) is kept in memory only.
+#[cfg(feature = "sqlite")]
+mod sqlite_store {
+    use super::{NodeStore, Sha256Hasher, StoredNode};
+    use rusqlite::{params, Connection};
+
+    struct SqliteNodeStore {
+        conn: Connection,
+    }
+
+    impl SqliteNodeStore {
+        fn open(path: &str) -> rusqlite::Result<Self> {
+            let conn = Connection::open(path)?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS nodes (
+                    hash BLOB PRIMARY KEY,
+                    kind INTEGER NOT NULL,
+                    data BLOB,
+                    left BLOB,
+                    middle BLOB,
+                    right BLOB
+                )",
+                [],
+            )?;
+            Ok(SqliteNodeStore { conn })
+        }
+    }
+
+    impl NodeStore<Sha256Hasher> for SqliteNodeStore {
+        fn get(&self, hash: &[u8; 32]) -> Option<StoredNode<Sha256Hasher>> {
+            self.conn.query_row(
+                "SELECT kind, data, left, middle, right FROM nodes WHERE hash = ?1",
+                params![hash.as_slice()],
+                |row| {
+                    let kind: i64 = row.get(0)?;
+                    if kind == 0 {
+                        let data: String = row.get(1)?;
+                        Ok(StoredNode::Leaf { data })
                     } else {
-                        false
+                        let left: Vec<u8> = row.get(2)?;
+                        let middle: Vec<u8> = row.get(3)?;
+                        let right: Vec<u8> = row.get(4)?;
+                        Ok(StoredNode::Branch {
+                            left: left.try_into().unwrap(),
+                            middle: middle.try_into().unwrap(),
+                            right: right.try_into().unwrap(),
+                        })
                     }
+                },
+            ).ok()
+        }
+
+        fn put(&mut self, hash: [u8; 32], node: StoredNode<Sha256Hasher>) {
+            match node {
+                StoredNode::Leaf { data } => {
+                    self.conn.execute(
+                        "INSERT OR REPLACE INTO nodes (hash, kind, data) VALUES (?1, 0, ?2)",
+                        params![hash.as_slice(), data],
+                    ).expect("sqlite insert should succeed");
+                }
+                StoredNode::Branch { left, middle, right } => {
+                    self.conn.execute(
+                        "INSERT OR REPLACE INTO nodes (hash, kind, left, middle, right) VALUES (?1, 1, ?2, ?3, ?4)",
+                        params![hash.as_slice(), left.as_slice(), middle.as_slice(), right.as_slice()],
+                    ).expect("sqlite insert should succeed");
                 }
             }
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn roundtrips_through_sqlite() {
+            let mut store = SqliteNodeStore::open(":memory:").unwrap();
+            let leaf = StoredNode::Leaf::<Sha256Hasher> { data: String::from("hello") };
+            store.put([1u8; 32], leaf);
+            assert!(matches!(store.get(&[1u8; 32]), Some(StoredNode::Leaf { .. })));
+        }
+    }
+}
+
+/// A ternary merkle tree stored as a single flat `Vec` of digests, level by
+/// level, rather than as boxed `Node`s. Avoids a heap allocation per node
+/// and lets a leaf's ancestors be found by arithmetic on level offsets
+/// instead of a recursive search over the whole tree.
+#[derive(Debug)]
+struct FlatMerkleTree<H: MerkleHasher = Sha256Hasher> {
+    /// All levels concatenated, leaves first, root last.
+    nodes: Vec<H::Digest>,
+    /// Start index of each level within `nodes`.
+    level_offsets: Vec<usize>,
+    /// Number of nodes in each level.
+    level_lens: Vec<usize>,
+}
+
+impl<H: MerkleHasher> FlatMerkleTree<H> {
+    /// Size of the level built from a level of `n` nodes, or `0` once `n`
+    /// is down to a single (root) node and no further level is needed.
+    fn next_level_len(n: usize) -> usize {
+        if n <= 1 {
+            0
+        } else {
+            n.div_ceil(3)
+        }
+    }
+
+    /// Total node count across every level, so the backing `Vec` can be
+    /// allocated once up front instead of growing as levels are built.
+    fn calculate_vec_capacity(leaf_count: usize) -> usize {
+        let mut total = 0;
+        let mut n = leaf_count;
+
+        while n > 0 {
+            total += n;
+            n = Self::next_level_len(n);
+        }
+
+        total
+    }
+
+    fn from_leaves(data: Vec<String>) -> Self {
+        let leaf_count = data.len();
+        let mut nodes = Vec::with_capacity(Self::calculate_vec_capacity(leaf_count));
+        let mut level_offsets = Vec::new();
+        let mut level_lens = Vec::new();
+
+        for d in &data {
+            nodes.push(H::hash_leaf(d.as_bytes()));
+        }
+
+        if leaf_count == 0 {
+            return FlatMerkleTree { nodes, level_offsets, level_lens };
+        }
+
+        level_offsets.push(0);
+        level_lens.push(leaf_count);
+
+        let mut level_start = 0;
+        let mut level_len = leaf_count;
+
+        while level_len > 1 {
+            let parent_start = nodes.len();
+
+            // `MerkleTree::build_tree` groups nodes by repeatedly popping
+            // from the end of the level rather than scanning it
+            // front-to-back, so a short trailing group is padded with a
+            // clone of the *last* node, not the first. Walk this level from
+            // its last node backwards in the same groups of three so a
+            // `FlatMerkleTree` built from the same leaves has the same root
+            // as a `MerkleTree`.
+            let mut i = level_len;
+
+            while i > 0 {
+                let left = nodes[level_start + i - 1].clone();
+                let middle = if i >= 2 { nodes[level_start + i - 2].clone() } else { left.clone() };
+                let right = if i >= 3 { nodes[level_start + i - 3].clone() } else { left.clone() };
+
+                nodes.push(H::hash_branch(&[&left, &middle, &right]));
+                i = i.saturating_sub(3);
+            }
+
+            let parent_len = nodes.len() - parent_start;
+            level_offsets.push(parent_start);
+            level_lens.push(parent_len);
+
+            level_start = parent_start;
+            level_len = parent_len;
+        }
+
+        FlatMerkleTree { nodes, level_offsets, level_lens }
+    }
+
+    fn root_hash(&self) -> Option<H::Digest> {
+        self.nodes.last().cloned()
+    }
+
+    fn proof(&self, data: &str) -> Option<Proof<H>> {
+        let target_hash = H::hash_leaf(data.as_bytes());
+        let leaf_count = *self.level_lens.first()?;
+        let mut index = self.nodes[..leaf_count].iter().position(|hash| *hash == target_hash)?;
+
+        let mut entries = Vec::new();
+
+        for level in 0..self.level_lens.len() - 1 {
+            let level_start = self.level_offsets[level];
+            let level_len = self.level_lens[level];
+
+            // Mirror `from_leaves`'s backward grouping: the group containing
+            // `index`, and its position within that group, are counted from
+            // the *last* node in the level rather than the first.
+            let dist_from_end = level_len - 1 - index;
+            let group = dist_from_end / 3;
+            let position_in_group = dist_from_end % 3;
+
+            let left_idx = level_len - 1 - group * 3;
+            let left = self.nodes[level_start + left_idx].clone();
+            let middle = if left_idx >= 1 { self.nodes[level_start + left_idx - 1].clone() } else { left.clone() };
+            let right = if left_idx >= 2 { self.nodes[level_start + left_idx - 2].clone() } else { left.clone() };
+
+            let (position, siblings) = match position_in_group {
+                0 => (Direction::Left, (middle, right)),
+                1 => (Direction::Middle, (left, right)),
+                _ => (Direction::Right, (left, middle)),
+            };
+
+            entries.push(ProofEntry { position, siblings });
+            index = group;
+        }
+
+        Some(Proof { target_hash, entries })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::MerkleTree;
+    use crate::{DefaultHashStrategy, FlatMerkleTree, InMemoryNodeStore, MerkleHasher, MerkleTree, SparseMerkleTree, VersionedMerkleTree};
+
+    #[test]
+    fn test_multiproof() {
+        let data = vec![
+            String::from("Hello"),
+            String::from("World"),
+            String::from("Merkle"),
+            String::from("Tree"),
+            String::from("Proof"),
+        ];
+
+        let merkle_tree = MerkleTree::<DefaultHashStrategy>::new(data);
+        let root = merkle_tree.root_hash().unwrap();
+
+        let partial_proof = merkle_tree.multiproof(&[1, 3]);
+        assert!(partial_proof.verify(&root));
+        // `build_tree` pads one of these target leaves into a duplicate
+        // sibling slot; the proof must still report exactly one target hash
+        // per requested index, not once per clone.
+        assert_eq!(partial_proof.target_hashes.len(), 2);
+
+        let mut tampered = merkle_tree.multiproof(&[1, 3]);
+        tampered.target_hashes[0] = merkle_tree.leaf_hashes[0];
+        assert!(!tampered.verify(&root));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let data = vec![
+            String::from("Hello"),
+            String::from("World"),
+            String::from("Merkle"),
+            String::from("Tree"),
+        ];
+
+        let merkle_tree = MerkleTree::<DefaultHashStrategy>::new(data);
+        let dot = merkle_tree.to_dot();
+        println!("{dot}");
+
+        assert!(dot.starts_with("digraph MerkleTree {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("style=dashed"));
+        assert!(dot.contains("Tree"));
+    }
+
+    #[test]
+    fn test_flat_tree() {
+        assert_eq!(FlatMerkleTree::<DefaultHashStrategy>::calculate_vec_capacity(4), 7);
+        assert_eq!(FlatMerkleTree::<DefaultHashStrategy>::calculate_vec_capacity(1), 1);
+        assert_eq!(FlatMerkleTree::<DefaultHashStrategy>::calculate_vec_capacity(0), 0);
+
+        let data = vec![
+            String::from("Hello"),
+            String::from("World"),
+            String::from("Merkle"),
+            String::from("Tree"),
+        ];
+
+        let flat_tree = FlatMerkleTree::<DefaultHashStrategy>::from_leaves(data.clone());
+        let root = flat_tree.root_hash().unwrap();
+
+        let proof = flat_tree.proof("Tree").expect("leaf should be present");
+        assert!(proof.verify(&root));
+
+        assert!(flat_tree.proof("Nope").is_none());
+
+        // Same leaves, same tree: a `FlatMerkleTree` is just a different
+        // memory layout for the tree `MerkleTree` builds, so their roots
+        // must agree however many leaves there are, padding or not.
+        for leaf_count in 1..=11 {
+            let data: Vec<String> = (0..leaf_count).map(|i| format!("leaf{i}")).collect();
+            let boxed_root = MerkleTree::<DefaultHashStrategy>::new(data.clone()).root_hash();
+            let flat_root = FlatMerkleTree::<DefaultHashStrategy>::from_leaves(data).root_hash();
+            assert_eq!(boxed_root, flat_root, "root mismatch for {leaf_count} leaves");
+        }
+    }
 
     #[test]
     fn test_1() {
@@ -165,11 +1089,77 @@ mod tests {
             String::from("Tree"),
         ];
 
-        let merkle_tree = MerkleTree::new(data);
+        let merkle_tree = MerkleTree::<DefaultHashStrategy>::new(data);
         println!("Root Hash: {:?}", merkle_tree.root_hash());
         println!("Merkle Tree: {:?}", merkle_tree);
 
-        let proof = merkle_tree.proof("Tree");
+        let proof = merkle_tree.proof("Tree").expect("leaf should be present");
         println!("{:?}", proof);
+
+        let root = merkle_tree.root_hash().unwrap();
+        assert!(proof.verify(&root));
+
+        // A proof must not verify against the wrong root...
+        assert!(!proof.verify(&merkle_tree.leaf_hashes[0]));
+
+        // ...nor with a tampered sibling hash.
+        let mut tampered = proof;
+        tampered.entries[0].siblings.0 = merkle_tree.leaf_hashes[0];
+        assert!(!tampered.verify(&root));
+    }
+
+    #[test]
+    fn test_default_hasher() {
+        let data = vec![
+            String::from("Hello"),
+            String::from("World"),
+            String::from("Merkle"),
+            String::from("Tree"),
+        ];
+
+        // No explicit type parameter: exercises the Sha256Hasher default.
+        let merkle_tree: MerkleTree = MerkleTree::new(data);
+
+        let proof = merkle_tree.proof("Tree").expect("leaf should be present");
+        assert!(proof.verify(&merkle_tree.root_hash().unwrap()));
+    }
+
+    #[test]
+    fn test_sparse_tree() {
+        let mut tree = SparseMerkleTree::<DefaultHashStrategy, 4>::new();
+        let empty_root = tree.root_hash();
+        assert_eq!(empty_root, tree.zero_hashes[4]);
+
+        let non_membership = tree.proof(42);
+        assert_eq!(non_membership.target_hash, tree.zero_hashes[0]);
+        assert!(non_membership.verify(&empty_root));
+
+        tree.insert(42, String::from("leaf-42"));
+        assert_ne!(tree.root_hash(), empty_root);
+
+        let membership = tree.proof(42);
+        assert_eq!(membership.target_hash, DefaultHashStrategy::hash_leaf("leaf-42".as_bytes()));
+        assert!(membership.verify(&tree.root_hash()));
+
+        let other_slot = tree.proof(7);
+        assert_eq!(other_slot.target_hash, tree.zero_hashes[0]);
+        assert!(other_slot.verify(&tree.root_hash()));
+    }
+
+    #[test]
+    fn test_versioned_tree() {
+        let mut tree = VersionedMerkleTree::<DefaultHashStrategy, InMemoryNodeStore<_>>::new(InMemoryNodeStore::new());
+
+        let root_v1 = tree.commit(vec![String::from("Hello"), String::from("World")]);
+        let root_v2 = tree.commit(vec![String::from("Hello"), String::from("Merkle")]);
+        assert_ne!(root_v1, root_v2);
+        assert_eq!(tree.roots, vec![root_v1, root_v2]);
+
+        let proof_v1 = tree.proof_at(&root_v1, "World").expect("leaf existed at v1");
+        assert!(proof_v1.verify(&root_v1));
+
+        assert!(tree.proof_at(&root_v2, "World").is_none());
+        let proof_v2 = tree.proof_at(&root_v2, "Merkle").expect("leaf existed at v2");
+        assert!(proof_v2.verify(&root_v2));
     }
 }